@@ -12,18 +12,273 @@
 // rules:
 // if no hangars available, the plane is not allowed to land
 // plane wants to take off after resting in hangar
+// on ctrl-c, stop generating new planes but let in-flight planes finish
+// operator can type "pause"/"resume"/"cancel" to steer the airport at runtime
 
 // metrics:
 // time taken to service all planes
 // qty of planes accepted
 // qty of planes denied
-// average time of service (from land to takeoff)
+// service time percentiles (from land to takeoff)
 
-use std::ops::Div;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::io::AsyncBufReadExt;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::Semaphore;
+use tokio::sync::{watch, Notify, Semaphore};
+
+/// Number of significant decimal digits kept within each magnitude. A value
+/// of 3 means every bucket's relative error is bounded to under 1%.
+const LATENCY_SIGNIFICANT_DIGITS: u32 = 3;
+
+/// A logarithmically-bucketed histogram for recording service-time latencies.
+///
+/// Values are grouped by magnitude (decimal exponent), and each magnitude is
+/// further split into `10^significant_digits` linear sub-buckets. This keeps
+/// memory bounded regardless of the value range while preserving a fixed
+/// relative error, unlike a plain `Vec<u128>` which grows with every sample
+/// and still only gives you a mean.
+#[derive(Debug)]
+struct LatencyHistogram {
+    sub_buckets_per_magnitude: u128,
+    buckets: Vec<Vec<u64>>,
+    total_count: u64,
+    sum: u128,
+    max: u128,
+}
+
+impl LatencyHistogram {
+    fn new(significant_digits: u32) -> Self {
+        Self {
+            sub_buckets_per_magnitude: 10u128.pow(significant_digits),
+            buckets: Vec::new(),
+            total_count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+
+    /// Returns the (magnitude, sub_bucket) coordinates for `value`, growing
+    /// the bucket storage to cover new magnitudes as needed.
+    fn locate(&mut self, value: u128) -> (usize, usize) {
+        let mut magnitude = 0usize;
+        let mut bucket_width = 1u128;
+        while value >= self.sub_buckets_per_magnitude * bucket_width {
+            bucket_width *= 10;
+            magnitude += 1;
+        }
+
+        while self.buckets.len() <= magnitude {
+            self.buckets
+                .push(vec![0; self.sub_buckets_per_magnitude as usize]);
+        }
+
+        let bucket_start = if magnitude == 0 {
+            0
+        } else {
+            self.sub_buckets_per_magnitude * bucket_width / 10
+        };
+        let sub_bucket = ((value - bucket_start) / bucket_width) as usize;
+        (magnitude, sub_bucket.min(self.buckets[magnitude].len() - 1))
+    }
+
+    /// The representative (lower-bound) value of a (magnitude, sub_bucket).
+    fn bucket_value(&self, magnitude: usize, sub_bucket: usize) -> u128 {
+        let bucket_width = 10u128.pow(magnitude as u32);
+        let bucket_start = if magnitude == 0 {
+            0
+        } else {
+            self.sub_buckets_per_magnitude * bucket_width / 10
+        };
+        bucket_start + sub_bucket as u128 * bucket_width
+    }
+
+    fn record(&mut self, value: u128) {
+        let (magnitude, sub_bucket) = self.locate(value);
+        self.buckets[magnitude][sub_bucket] += 1;
+        self.total_count += 1;
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    fn mean(&self) -> f64 {
+        if self.total_count == 0 {
+            return 0.0;
+        }
+        self.sum as f64 / self.total_count as f64
+    }
+
+    /// The exact maximum recorded value, bypassing the bucket approximation.
+    fn max(&self) -> u128 {
+        self.max
+    }
+
+    /// Walks buckets in ascending order accumulating counts until the
+    /// cumulative count reaches `q * total_count`, then returns that
+    /// bucket's representative value.
+    fn quantile(&self, q: f64) -> u128 {
+        if self.total_count == 0 {
+            return 0;
+        }
+
+        let target = (q * self.total_count as f64).ceil() as u64;
+        let mut cumulative = 0u64;
+        for (magnitude, sub_buckets) in self.buckets.iter().enumerate() {
+            for (sub_bucket, &count) in sub_buckets.iter().enumerate() {
+                cumulative += count;
+                if cumulative >= target {
+                    return self.bucket_value(magnitude, sub_bucket);
+                }
+            }
+        }
+
+        self.max
+    }
+}
+
+/// Identifies a single `PlaneWorker` for the lifetime of the simulation.
+type WorkerId = u64;
+
+/// The lifecycle states a `PlaneWorker` moves through on its way to the gate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WorkerState {
+    WaitingForRunway,
+    Landing,
+    Resting,
+    TakingOff,
+    Done,
+    Denied,
+}
+
+impl WorkerState {
+    fn is_terminal(self) -> bool {
+        matches!(self, WorkerState::Done | WorkerState::Denied)
+    }
+}
+
+/// Central registry of every `PlaneWorker` that has ever been spawned,
+/// giving the simulation an introspection API instead of a pile of detached
+/// `tokio::spawn` tasks nobody can ask about.
+#[derive(Debug, Default)]
+struct WorkerManager {
+    next_id: AtomicU64,
+    workers: Mutex<HashMap<WorkerId, WorkerState>>,
+}
+
+impl WorkerManager {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a new worker in the `WaitingForRunway` state and returns
+    /// the id it should use for every subsequent `set_state` call.
+    fn register(&self) -> WorkerId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.workers
+            .lock()
+            .unwrap()
+            .insert(id, WorkerState::WaitingForRunway);
+        id
+    }
+
+    fn set_state(&self, id: WorkerId, state: WorkerState) {
+        self.workers.lock().unwrap().insert(id, state);
+    }
+
+    /// Marks a worker as dead after its task has finished. A task that
+    /// returns normally is trusted to have already reported its own
+    /// terminal state; a task that panicked never got the chance, so it is
+    /// forced into `Denied` here.
+    fn reap(&self, id: WorkerId, outcome: Result<(), tokio::task::JoinError>) {
+        if outcome.is_err() {
+            self.set_state(id, WorkerState::Denied);
+        }
+    }
+
+    /// Snapshots every worker the manager knows about along with its
+    /// current state, e.g. to report how many planes are resting vs.
+    /// queued at any instant.
+    fn snapshot(&self) -> Vec<(WorkerId, WorkerState)> {
+        self.workers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, &state)| (id, state))
+            .collect()
+    }
+
+    fn live_count(&self) -> usize {
+        self.workers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|state| !state.is_terminal())
+            .count()
+    }
+}
+
+/// What happens to a newly-arrived plane while the airport is paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PausePolicy {
+    /// Hold a hangar and wait for the airport to resume.
+    Queue,
+    /// Turn the plane away immediately.
+    Deny,
+}
+
+/// An operator command for the control channel below.
+#[derive(Debug, Clone, Copy)]
+enum ControlCommand {
+    Pause,
+    Resume,
+    Cancel,
+}
+
+/// Gates whether `PlaneWorker`s may contend for a runway. Paused workers
+/// park on the shared `Notify` instead of polling, and wake in bulk as soon
+/// as `resume` flips the flag back.
+#[derive(Debug, Default)]
+struct AirportGate {
+    paused: AtomicBool,
+    notify: Notify,
+}
+
+impl AirportGate {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Parks here while the gate is paused. Registers for notification
+    /// before re-checking the flag so a `resume` landing between the check
+    /// and the park can't be missed.
+    async fn wait_while_paused(&self) {
+        loop {
+            if !self.is_paused() {
+                return;
+            }
+            let notified = self.notify.notified();
+            if !self.is_paused() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Plane {
@@ -48,50 +303,208 @@ impl Default for Plane {
     }
 }
 
-async fn plane_generator(number_of_planes: usize, interval: Duration, sender: Sender<Plane>) {
+/// Emits up to `number_of_planes` planes on `interval`, stopping early (and
+/// returning how many it actually sent) if `shutdown` flips to `true`.
+async fn plane_generator(
+    number_of_planes: usize,
+    interval: Duration,
+    sender: Sender<Plane>,
+    mut shutdown: watch::Receiver<bool>,
+) -> usize {
     let mut interval = tokio::time::interval(interval);
+    let mut planes_sent = 0;
     for i in 0..number_of_planes {
-        interval.tick().await;
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.changed() => {
+                println!("shutdown requested, no more planes will be generated");
+                break;
+            }
+        }
+
+        // Block naturally when the arrivals queue is saturated instead of
+        // racing a `try_send` against the consumer and panicking on loss.
+        let permit;
+        tokio::select! {
+            result = sender.reserve() => {
+                match result {
+                    Ok(p) => permit = p,
+                    Err(_) => break,
+                }
+            }
+            _ = shutdown.changed() => {
+                println!("shutdown requested while waiting for arrivals capacity");
+                break;
+            }
+        }
+
         println!("sending plane: {i}");
-        if sender.try_send(Plane::default()).is_err() {
-            panic!("Plane is gone! This should never happen");
+        permit.send(Plane::default());
+        planes_sent += 1;
+    }
+    planes_sent
+}
+
+/// Paces the runway so it stays busy at most `1 / (1 + factor)` of
+/// wall-clock time, e.g. to model a maintenance window or a noise curfew.
+/// A factor of 0 runs at full speed; a factor of 4 keeps the runway busy
+/// at most 20% of the time.
+///
+/// Callers report measured wall-clock time, so an operation that runs long
+/// is followed by a proportionally longer sleep; the running sums keep the
+/// total injected sleep at `factor` times the total active time. The time
+/// actually spent asleep is not measured, so an oversleep is not deducted
+/// from the next sleep.
+struct Tranquilizer {
+    factor: u32,
+    active_time: Duration,
+    injected_sleep: Duration,
+}
+
+impl Tranquilizer {
+    fn new(factor: u32) -> Self {
+        Self {
+            factor,
+            active_time: Duration::ZERO,
+            injected_sleep: Duration::ZERO,
+        }
+    }
+
+    /// Records `active` wall-clock time and returns how long to sleep to
+    /// keep the active fraction converging to `1 / (1 + factor)`.
+    fn note_active(&mut self, active: Duration) -> Duration {
+        self.active_time += active;
+        if self.factor == 0 {
+            return Duration::ZERO;
         }
+
+        let target_sleep = self.active_time * self.factor;
+        let sleep_for = target_sleep.saturating_sub(self.injected_sleep);
+        self.injected_sleep += sleep_for;
+        sleep_for
+    }
+}
+
+/// Records `active` against the shared tranquilizer and sleeps off
+/// whatever idle time it's owed, without holding the lock across the
+/// `.await`.
+async fn tranquilize(tranquilizer: &Mutex<Tranquilizer>, active: Duration) {
+    let sleep_for = tranquilizer.lock().unwrap().note_active(active);
+    if !sleep_for.is_zero() {
+        tokio::time::sleep(sleep_for).await;
     }
 }
 
-async fn plane_receiver(
-    available_runways: Arc<Semaphore>,
-    available_hangars: Arc<Semaphore>,
-    plane: Plane,
-    done_sender: Sender<Plane>,
-) {
-    let runway_permit = available_runways.try_acquire();
-    if runway_permit.is_err() {
-        println!("no runway available!");
-        return;
+/// Drives a single `Plane` through the land/rest/take-off cycle, reporting
+/// every transition to the shared `WorkerManager` so the rest of the
+/// simulation can see what it's doing.
+struct PlaneWorker {
+    id: WorkerId,
+    manager: Arc<WorkerManager>,
+}
+
+impl PlaneWorker {
+    fn new(manager: Arc<WorkerManager>) -> Self {
+        Self {
+            id: manager.register(),
+            manager,
+        }
     }
 
-    let hangar_permit = available_hangars.try_acquire();
-    if hangar_permit.is_err() {
-        println!("no hangars left!");
-        return;
+    fn set_state(&self, state: WorkerState) {
+        self.manager.set_state(self.id, state);
     }
 
-    tokio::time::sleep(plane.time_to_land).await;
+    #[allow(clippy::too_many_arguments)]
+    async fn run(
+        self,
+        available_runways: Arc<Semaphore>,
+        available_hangars: Arc<Semaphore>,
+        tranquilizer: Arc<Mutex<Tranquilizer>>,
+        gate: Arc<AirportGate>,
+        pause_policy: PausePolicy,
+        plane: Plane,
+        done_sender: Sender<Plane>,
+    ) {
+        self.set_state(WorkerState::WaitingForRunway);
+
+        // While paused, a newly-arrived plane either holds a hangar and
+        // waits for resume, or is turned away outright.
+        let queued_hangar_permit = if gate.is_paused() {
+            match pause_policy {
+                PausePolicy::Deny => {
+                    println!("airport is paused, plane denied!");
+                    self.set_state(WorkerState::Denied);
+                    return;
+                }
+                PausePolicy::Queue => {
+                    let permit = available_hangars.acquire().await.unwrap();
+                    gate.wait_while_paused().await;
+                    Some(permit)
+                }
+            }
+        } else {
+            None
+        };
+
+        // A plane that queued through a pause already waited out the whole
+        // point of queueing, so give it a real (blocking) shot at the
+        // runway instead of a single non-blocking check that nearly always
+        // loses when every queued plane wakes up and contends at once.
+        let runway_permit = if queued_hangar_permit.is_some() {
+            available_runways.acquire().await.ok()
+        } else {
+            available_runways.try_acquire().ok()
+        };
+        let runway_permit = match runway_permit {
+            Some(permit) => permit,
+            None => {
+                println!("no runway available!");
+                self.set_state(WorkerState::Denied);
+                return;
+            }
+        };
+
+        let hangar_permit = match queued_hangar_permit {
+            Some(permit) => permit,
+            None => match available_hangars.try_acquire() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    println!("no hangars left!");
+                    self.set_state(WorkerState::Denied);
+                    return;
+                }
+            },
+        };
 
-    drop(runway_permit);
+        self.set_state(WorkerState::Landing);
+        let landing_started = Instant::now();
+        tokio::time::sleep(plane.time_to_land).await;
 
-    tokio::time::sleep(plane.time_to_rest).await;
+        // The runway stays held through the cooldown so the pacing limits
+        // the runway itself, not just this plane.
+        tranquilize(&tranquilizer, landing_started.elapsed()).await;
+        drop(runway_permit);
 
-    let runway_permit = available_runways.acquire().await.unwrap();
+        self.set_state(WorkerState::Resting);
+        tokio::time::sleep(plane.time_to_rest).await;
 
-    drop(hangar_permit);
+        self.set_state(WorkerState::WaitingForRunway);
+        gate.wait_while_paused().await;
+        let runway_permit = available_runways.acquire().await.unwrap();
 
-    tokio::time::sleep(plane.time_to_land).await;
+        drop(hangar_permit);
 
-    drop(runway_permit);
+        self.set_state(WorkerState::TakingOff);
+        let takeoff_started = Instant::now();
+        tokio::time::sleep(plane.time_to_land).await;
 
-    done_sender.send(plane).await.unwrap();
+        tranquilize(&tranquilizer, takeoff_started.elapsed()).await;
+        drop(runway_permit);
+
+        self.set_state(WorkerState::Done);
+        done_sender.send(plane).await.unwrap();
+    }
 }
 
 #[tokio::main]
@@ -100,44 +513,160 @@ async fn main() {
     const QTY_RUNWAYS: usize = 1;
     const QTY_HANGARS: usize = 3;
     const PLANE_INTERVAL: Duration = Duration::from_secs(1);
+    // How many planes can queue up for a runway before the generator
+    // itself starts blocking. Raise this to see how denied-plane counts
+    // change as the arrivals queue grows.
+    const ARRIVALS_QUEUE_DEPTH: usize = 1;
+    // Runway busy fraction converges to 1 / (1 + TRANQUILITY); 0 is full speed.
+    const TRANQUILITY: u32 = 0;
+    // What a newly-arrived plane does while the airport is paused.
+    const PAUSE_POLICY: PausePolicy = PausePolicy::Queue;
 
-    let (arrivals_tx, mut arrivals_rx) = tokio::sync::mpsc::channel::<Plane>(1);
+    let (arrivals_tx, mut arrivals_rx) = tokio::sync::mpsc::channel::<Plane>(ARRIVALS_QUEUE_DEPTH);
     let (departures_tx, mut departures_rx) = tokio::sync::mpsc::channel::<Plane>(TOTAL_PLANES);
+    let (control_tx, mut control_rx) = tokio::sync::mpsc::channel::<ControlCommand>(8);
 
     let available_runways = Arc::new(Semaphore::new(QTY_RUNWAYS));
     let available_hangars = Arc::new(Semaphore::new(QTY_HANGARS));
+    let tranquilizer = Arc::new(Mutex::new(Tranquilizer::new(TRANQUILITY)));
+    let gate = Arc::new(AirportGate::new());
+    let worker_manager = Arc::new(WorkerManager::new());
+    let arrivals_worker_manager = worker_manager.clone();
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let shutdown_triggered = Arc::new(AtomicBool::new(false));
+    let in_flight_at_shutdown = Arc::new(AtomicUsize::new(0));
 
-    tokio::spawn(plane_generator(TOTAL_PLANES, PLANE_INTERVAL, arrivals_tx));
+    tokio::spawn({
+        let gate = gate.clone();
+        let worker_manager = worker_manager.clone();
+        let shutdown_triggered = shutdown_triggered.clone();
+        let in_flight_at_shutdown = in_flight_at_shutdown.clone();
+        let shutdown_tx = shutdown_tx.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                shutdown_triggered.store(true, Ordering::SeqCst);
+                in_flight_at_shutdown.store(worker_manager.live_count(), Ordering::SeqCst);
+                // Wake any worker parked in `wait_while_paused` so the
+                // drain below can actually observe them finishing.
+                gate.resume();
+                let _ = shutdown_tx.send(true);
+            }
+        }
+    });
+
+    // Reads "pause" / "resume" / "cancel" lines from stdin so an operator
+    // can steer the simulation at runtime instead of just watching it run.
+    tokio::spawn({
+        let control_tx = control_tx.clone();
+        async move {
+            let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command = match line.trim() {
+                    "pause" => Some(ControlCommand::Pause),
+                    "resume" => Some(ControlCommand::Resume),
+                    "cancel" => Some(ControlCommand::Cancel),
+                    _ => None,
+                };
+                if let Some(command) = command {
+                    if control_tx.send(command).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    tokio::spawn({
+        let gate = gate.clone();
+        let worker_manager = worker_manager.clone();
+        let shutdown_triggered = shutdown_triggered.clone();
+        let in_flight_at_shutdown = in_flight_at_shutdown.clone();
+        async move {
+            while let Some(command) = control_rx.recv().await {
+                match command {
+                    ControlCommand::Pause => {
+                        println!("airport paused");
+                        gate.pause();
+                    }
+                    ControlCommand::Resume => {
+                        println!("airport resumed");
+                        gate.resume();
+                    }
+                    ControlCommand::Cancel => {
+                        println!("airport cancelled");
+                        shutdown_triggered.store(true, Ordering::SeqCst);
+                        in_flight_at_shutdown.store(worker_manager.live_count(), Ordering::SeqCst);
+                        // Wake any worker parked in `wait_while_paused` so
+                        // the drain below can actually observe them finishing.
+                        gate.resume();
+                        let _ = shutdown_tx.send(true);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let generator_handle = tokio::spawn(plane_generator(
+        TOTAL_PLANES,
+        PLANE_INTERVAL,
+        arrivals_tx,
+        shutdown_rx,
+    ));
 
     tokio::spawn(async move {
         while let Some(plane) = arrivals_rx.recv().await {
-            tokio::spawn(plane_receiver(
+            let worker = PlaneWorker::new(arrivals_worker_manager.clone());
+            let id = worker.id;
+            let manager = arrivals_worker_manager.clone();
+            let handle = tokio::spawn(worker.run(
                 available_runways.clone(),
                 available_hangars.clone(),
+                tranquilizer.clone(),
+                gate.clone(),
+                PAUSE_POLICY,
                 plane,
                 departures_tx.clone(),
             ));
+            tokio::spawn(async move {
+                let outcome = handle.await;
+                manager.reap(id, outcome);
+            });
         }
     });
 
-    let mut service_times = Vec::with_capacity(TOTAL_PLANES / 2);
+    let mut service_times = LatencyHistogram::new(LATENCY_SIGNIFICANT_DIGITS);
 
     while let Some(plane) = departures_rx.recv().await {
         let service_time = plane.created_at.elapsed().as_millis();
-        println!(
-            "received a plane, service time: {}",
-            service_time
-        );
-        service_times.push(service_time);
+        println!("received a plane, service time: {}", service_time);
+        service_times.record(service_time);
     }
 
-    let qty_accepted_planes = service_times.len();
+    let planes_generated = generator_handle.await.unwrap_or(0);
+    let qty_accepted_planes = service_times.total_count as usize;
+    let qty_denied_planes = worker_manager
+        .snapshot()
+        .iter()
+        .filter(|(_, state)| *state == WorkerState::Denied)
+        .count();
 
     println!();
     println!("--------------------------------");
+    println!("planes generated: {}", planes_generated);
     println!("accepted planes: {}", qty_accepted_planes);
-    println!("denied planes: {}", TOTAL_PLANES - qty_accepted_planes);
-    println!("service times: {service_times:?}");
-    println!("avg service time: {}", service_times.iter().sum::<u128>().div(qty_accepted_planes as u128));
+    println!("denied planes: {}", qty_denied_planes);
+    if shutdown_triggered.load(Ordering::SeqCst) {
+        println!(
+            "planes still in the air at shutdown: {}",
+            in_flight_at_shutdown.load(Ordering::SeqCst)
+        );
+    }
+    println!("service time p50: {}ms", service_times.quantile(0.50));
+    println!("service time p90: {}ms", service_times.quantile(0.90));
+    println!("service time p99: {}ms", service_times.quantile(0.99));
+    println!("service time max: {}ms", service_times.max());
+    println!("avg service time: {:.2}ms", service_times.mean());
     println!("--------------------------------");
 }